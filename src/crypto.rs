@@ -0,0 +1,211 @@
+use crate::{KvError, Result};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key as AesKey, Nonce as AesNonce};
+use argon2::Argon2;
+use chacha20poly1305::{ChaCha20Poly1305, Key as ChaChaKey, Nonce as ChaChaNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// The AEAD cipher used to encrypt log values when a store is opened with a
+/// passphrase, recorded as a byte in the store's `keyfile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+	/// AES-256-GCM
+	Aes256Gcm,
+	/// ChaCha20-Poly1305
+	ChaCha20Poly1305,
+}
+
+impl Cipher {
+	fn to_byte(self) -> u8 {
+		match self {
+			Cipher::Aes256Gcm => 0,
+			Cipher::ChaCha20Poly1305 => 1,
+		}
+	}
+
+	fn from_byte(byte: u8) -> Result<Cipher> {
+		match byte {
+			0 => Ok(Cipher::Aes256Gcm),
+			1 => Ok(Cipher::ChaCha20Poly1305),
+			_ => Err(KvError::Decryption),
+		}
+	}
+}
+
+fn keyfile_path(dirpath: &PathBuf) -> PathBuf {
+	dirpath.join("keyfile")
+}
+
+/// The cipher choice and salt persisted in a store's `keyfile`, generated
+/// once when an encrypted store is first created.
+struct Keyfile {
+	cipher: Cipher,
+	salt: [u8; SALT_LEN],
+}
+
+impl Keyfile {
+	fn generate(cipher: Cipher) -> Keyfile {
+		let mut salt = [0u8; SALT_LEN];
+		OsRng.fill_bytes(&mut salt);
+		Keyfile { cipher, salt }
+	}
+
+	fn load(path: &PathBuf) -> Result<Option<Keyfile>> {
+		let bytes = match fs::read(path) {
+			Ok(bytes) => bytes,
+			Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(err.into()),
+		};
+		if bytes.len() != 1 + SALT_LEN {
+			return Err(KvError::Decryption);
+		}
+		let cipher = Cipher::from_byte(bytes[0])?;
+		let mut salt = [0u8; SALT_LEN];
+		salt.copy_from_slice(&bytes[1..]);
+		Ok(Some(Keyfile { cipher, salt }))
+	}
+
+	fn save(&self, path: &PathBuf) -> Result<()> {
+		let mut bytes = Vec::with_capacity(1 + SALT_LEN);
+		bytes.push(self.cipher.to_byte());
+		bytes.extend_from_slice(&self.salt);
+		fs::write(path, bytes)?;
+		Ok(())
+	}
+
+	fn derive_key(&self, passphrase: &str) -> [u8; KEY_LEN] {
+		let mut key = [0u8; KEY_LEN];
+		Argon2::default()
+			.hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+			.expect("KEY_LEN is a valid argon2 output length");
+		key
+	}
+}
+
+/// A derived AEAD key and cipher choice used to encrypt/decrypt log
+/// payloads. Built by `open_encrypted` from a store's `keyfile` and a
+/// passphrase; never persisted itself.
+pub(crate) struct Encryption {
+	cipher: Cipher,
+	key: [u8; KEY_LEN],
+}
+
+impl Encryption {
+	/// Loads (or, on first use, creates) the `keyfile` in `dirpath` and
+	/// derives the encryption key from `passphrase`. `cipher` selects the
+	/// AEAD algorithm for a brand-new store; it's ignored if a `keyfile`
+	/// already records one.
+	pub(crate) fn open(dirpath: &PathBuf, passphrase: &str, cipher: Cipher) -> Result<Encryption> {
+		let path = keyfile_path(dirpath);
+		let keyfile = match Keyfile::load(&path)? {
+			Some(keyfile) => keyfile,
+			None => {
+				let keyfile = Keyfile::generate(cipher);
+				keyfile.save(&path)?;
+				keyfile
+			}
+		};
+		let key = keyfile.derive_key(passphrase);
+		Ok(Encryption {
+			cipher: keyfile.cipher,
+			key,
+		})
+	}
+
+	/// Encrypts `plaintext` with a fresh random nonce, returning
+	/// `nonce || ciphertext` ready to be written as a log record's payload.
+	pub(crate) fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+		let mut nonce_bytes = [0u8; NONCE_LEN];
+		OsRng.fill_bytes(&mut nonce_bytes);
+
+		let ciphertext = match self.cipher {
+			Cipher::Aes256Gcm => {
+				let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(&self.key));
+				cipher
+					.encrypt(AesNonce::from_slice(&nonce_bytes), plaintext)
+					.expect("AES-256-GCM encryption of a log record should not fail")
+			}
+			Cipher::ChaCha20Poly1305 => {
+				let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&self.key));
+				cipher
+					.encrypt(ChaChaNonce::from_slice(&nonce_bytes), plaintext)
+					.expect("ChaCha20-Poly1305 encryption of a log record should not fail")
+			}
+		};
+
+		let mut frame = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+		frame.extend_from_slice(&nonce_bytes);
+		frame.extend_from_slice(&ciphertext);
+		frame
+	}
+
+	/// Splits a `nonce || ciphertext` frame and decrypts it, returning
+	/// `KvError::Decryption` instead of `KvError::UnexpectedAction` when the
+	/// frame is too short or the AEAD tag doesn't verify.
+	pub(crate) fn open_frame(&self, frame: &[u8]) -> Result<Vec<u8>> {
+		if frame.len() < NONCE_LEN {
+			return Err(KvError::Decryption);
+		}
+		let (nonce_bytes, ciphertext) = frame.split_at(NONCE_LEN);
+
+		match self.cipher {
+			Cipher::Aes256Gcm => {
+				let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(&self.key));
+				cipher
+					.decrypt(AesNonce::from_slice(nonce_bytes), ciphertext)
+					.map_err(|_| KvError::Decryption)
+			}
+			Cipher::ChaCha20Poly1305 => {
+				let cipher = ChaCha20Poly1305::new(ChaChaKey::from_slice(&self.key));
+				cipher
+					.decrypt(ChaChaNonce::from_slice(nonce_bytes), ciphertext)
+					.map_err(|_| KvError::Decryption)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	fn round_trip(cipher: Cipher) {
+		let dir = TempDir::new().unwrap();
+		let encryption = Encryption::open(&dir.path().to_path_buf(), "hunter2", cipher).unwrap();
+
+		let plaintext: &[u8] = b"the quick brown fox";
+		let sealed = encryption.seal(plaintext);
+		assert_ne!(sealed.as_slice(), plaintext);
+		assert_eq!(encryption.open_frame(&sealed).unwrap().as_slice(), plaintext);
+	}
+
+	#[test]
+	fn aes_256_gcm_round_trip() {
+		round_trip(Cipher::Aes256Gcm);
+	}
+
+	#[test]
+	fn chacha20_poly1305_round_trip() {
+		round_trip(Cipher::ChaCha20Poly1305);
+	}
+
+	#[test]
+	fn reopening_with_the_wrong_passphrase_fails_to_decrypt() {
+		let dir = TempDir::new().unwrap();
+		let dirpath = dir.path().to_path_buf();
+		let encryption = Encryption::open(&dirpath, "hunter2", Cipher::Aes256Gcm).unwrap();
+		let sealed = encryption.seal(b"secret");
+
+		let wrong = Encryption::open(&dirpath, "letmein", Cipher::Aes256Gcm).unwrap();
+		assert!(matches!(wrong.open_frame(&sealed), Err(KvError::Decryption)));
+	}
+}
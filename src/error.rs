@@ -8,11 +8,19 @@ pub enum KvError {
   Io(io::Error),
   /// Serialization or deserialization error
   Serde(serde_json::Error),
+  /// Serialization or deserialization error from the `BincodeCodec`
+  Bincode(bincode::Error),
   /// Removing non-existent key error
   KeyNotFound,
   /// Unexpected action error.
   /// It indicated a corrupted log or a program bug.
   UnexpectedAction,
+  /// A record's CRC32 checksum did not match its payload, indicating the
+  /// log was truncated or corrupted on disk.
+  ChecksumMismatch,
+  /// An encrypted store's `keyfile` was malformed, or an AEAD
+  /// record failed to decrypt (wrong passphrase, or corruption).
+  Decryption,
   /// Unable to glob dirpath error
   PatternError(glob::PatternError),
 }
@@ -29,6 +37,12 @@ impl From<serde_json::Error> for KvError {
   }
 }
 
+impl From<bincode::Error> for KvError {
+  fn from(err: bincode::Error) -> KvError {
+    KvError::Bincode(err)
+  }
+}
+
 impl From<glob::PatternError> for KvError {
   fn from(err: glob::PatternError) -> KvError {
     KvError::PatternError(err)
@@ -1,28 +1,199 @@
+use crate::crypto::Encryption;
 use crate::kvreader::KvReader;
 use crate::kvwriter::KvWriter;
-use crate::{KvError, Result};
+use crate::{Cipher, Codec, JsonCodec, KvError, KvsEngine, Result};
 use glob::glob;
+use memmap2::Mmap;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Deserializer;
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fs;
+use std::hash::Hash;
 use std::io;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 use std::path::PathBuf;
 
 const THRESHOLD: u64 = 1024 * 1024;
 
+/// Size in bytes of the fixed header written before every serialized
+/// `KvAction`: a little-endian `u32` payload length followed by a
+/// little-endian `u32` CRC32 of the payload.
+const FRAME_HEADER_LEN: u64 = 8;
+
+/// Largest payload `read_frame` will allocate for. A corrupted length
+/// field (a single flipped bit, or arbitrary bytes from a pre-framing log)
+/// can otherwise claim up to 4 GiB before the CRC is even checked; rejecting
+/// anything past this cap turns that into a clean `ChecksumMismatch`
+/// instead of an OOM.
+const MAX_RECORD_LEN: usize = 64 * 1024 * 1024;
+
+/// Contents of a store's `version` marker file once it's on the framed
+/// on-disk format (see `write_frame`/`read_frame` and
+/// `migrate_legacy_store`).
+const FORMAT_VERSION: u8 = 1;
+
 #[derive(Debug, Serialize, Deserialize)]
-enum KvAction {
-	Set(String, String),
-	Remove(String),
+enum KvAction<K, V> {
+	Set(K, V),
+	Remove(K),
+}
+
+/// Writes `payload` as a framed record: a length + CRC32 header followed by
+/// the bytes themselves, so corruption can be detected on read without
+/// relying on `serde_json` to fail loudly.
+fn write_frame(writer: &mut impl Write, payload: &[u8]) -> Result<()> {
+	let length = payload.len() as u32;
+	let crc = crc32fast::hash(payload);
+	writer.write_all(&length.to_le_bytes())?;
+	writer.write_all(&crc.to_le_bytes())?;
+	writer.write_all(payload)?;
+	Ok(())
+}
+
+/// Reads one framed record from `reader`, verifying its CRC32. Returns
+/// `Ok(None)` on a clean EOF (no more records), or
+/// `Err(KvError::ChecksumMismatch)` if the payload doesn't match its header.
+fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+	let mut header = [0u8; FRAME_HEADER_LEN as usize];
+	let mut filled = 0;
+	while filled < header.len() {
+		match reader.read(&mut header[filled..]) {
+			Ok(0) if filled == 0 => return Ok(None),
+			Ok(0) => return Err(KvError::ChecksumMismatch),
+			Ok(n) => filled += n,
+			Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+			Err(err) => return Err(err.into()),
+		}
+	}
+	let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+	let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+	if length > MAX_RECORD_LEN {
+		return Err(KvError::ChecksumMismatch);
+	}
+
+	let mut payload = vec![0u8; length];
+	reader.read_exact(&mut payload)?;
+	if crc32fast::hash(&payload) != crc {
+		return Err(KvError::ChecksumMismatch);
+	}
+
+	Ok(Some(payload))
 }
 
 fn generation_path(dirpath: &PathBuf, generation: u64) -> PathBuf {
 	dirpath.join(format!("{}.db", generation))
 }
 
+fn hint_path(dirpath: &PathBuf, generation: u64) -> PathBuf {
+	dirpath.join(format!("{}.hint", generation))
+}
+
+fn version_path(dirpath: &PathBuf) -> PathBuf {
+	dirpath.join("version")
+}
+
+/// Migrates a store directory that predates framed records.
+///
+/// A directory with `.db` files but no `version` marker either predates
+/// `write_frame`/`read_frame` entirely (plain `serde_json` streams of
+/// `KvAction<K, V>`, no length/CRC header), or was written by a build that
+/// already framed records but ran before the `version` marker existed;
+/// `is_framed` tells the two apart by sniffing each generation's first
+/// record. Only the genuinely unframed case is replayed with the legacy
+/// reader to reconstruct its live key/value pairs and rewritten as framed
+/// records into a fresh generation 0 before the old generations are
+/// removed; an already-framed directory (and a brand-new one with no `.db`
+/// files yet) is simply stamped as up to date. Once a `version` marker
+/// exists, this is a no-op, so the migration only ever runs once per store.
+fn migrate_legacy_store<K, V, C>(
+	path: &PathBuf,
+	generations: &[u64],
+	encryption: Option<&Encryption>,
+) -> Result<()>
+where
+	K: Serialize + DeserializeOwned + Eq + Hash + Clone,
+	V: Serialize + DeserializeOwned,
+	C: Codec,
+{
+	let version_path = version_path(path);
+	if version_path.exists() {
+		return Ok(());
+	}
+
+	let already_framed = generations.iter().all(|&gen| is_framed(path, gen));
+	if !generations.is_empty() && !already_framed {
+		let mut live: HashMap<K, V> = HashMap::new();
+		for &gen in generations {
+			let file = fs::File::open(generation_path(path, gen))?;
+			let stream =
+				Deserializer::from_reader(BufReader::new(file)).into_iter::<KvAction<K, V>>();
+			for action in stream {
+				match action? {
+					KvAction::Set(key, value) => {
+						live.insert(key, value);
+					}
+					KvAction::Remove(key) => {
+						live.remove(&key);
+					}
+				}
+			}
+		}
+
+		let tmp_path = path.join("migrate.tmp");
+		let _ = fs::remove_file(&tmp_path);
+		let mut writer = KvWriter::new(&tmp_path)?;
+		for (key, value) in live {
+			let action = KvAction::Set(key, value);
+			let plaintext = C::encode(&action)?;
+			let payload = match encryption {
+				Some(encryption) => encryption.seal(&plaintext),
+				None => plaintext,
+			};
+			write_frame(&mut writer, &payload)?;
+		}
+		writer.flush()?;
+		drop(writer);
+
+		for &gen in generations {
+			fs::remove_file(generation_path(path, gen))?;
+			let _ = fs::remove_file(hint_path(path, gen));
+		}
+		fs::rename(&tmp_path, generation_path(path, 0))?;
+	}
+
+	fs::write(version_path, [FORMAT_VERSION])?;
+	Ok(())
+}
+
+/// Maps a generation's `.db` file into memory for zero-copy reads. The
+/// mapping only covers the file as it was when it was created, so a mapping
+/// of the generation still being appended to must be dropped after each
+/// write and re-created (see `invalidate_active_mmap`).
+fn mmap_generation(path: &PathBuf) -> Result<Mmap> {
+	let file = fs::File::open(path)?;
+	let mmap = unsafe { Mmap::map(&file)? };
+	Ok(mmap)
+}
+
+/// Whether `generation`'s `.db` file already starts with a valid framed
+/// record (or is empty), as opposed to the plain `serde_json` stream that
+/// predates `write_frame`/`read_frame`. Used by `migrate_legacy_store` to
+/// tell a store that's already on the framed format (but hasn't been
+/// stamped with a `version` marker yet, e.g. one written by an older build
+/// of this format) apart from a genuinely pre-framing store, since both
+/// look the same from the presence of the marker file alone.
+fn is_framed(dirpath: &PathBuf, generation: u64) -> bool {
+	let file = match fs::File::open(generation_path(dirpath, generation)) {
+		Ok(file) => file,
+		Err(_) => return true,
+	};
+	read_frame(&mut BufReader::new(file)).is_ok()
+}
+
 fn active_generations(dirpath: &PathBuf) -> Result<Vec<u64>> {
 	let glob_pattern = format!("{}/*.db", &dirpath.to_str().expect("dirpath not utf8"));
 	let generation_pattern = Regex::new(r"/(\d+)\.db$").unwrap();
@@ -48,29 +219,92 @@ struct KvIndexRecord {
 	length: u64,
 }
 
-type KvIndex = HashMap<String, KvIndexRecord>;
+type KvIndex<K> = HashMap<K, KvIndexRecord>;
 
-/// The `KvStore` stores string key/value pairs.
+/// On-disk representation of a single live `KvIndexRecord`, used by the
+/// `<generation>.hint` files so `open` can rebuild the index without
+/// replaying the whole log. Always JSON-encoded, independent of the store's
+/// `Codec`, since it only ever holds a key and a couple of integers.
+#[derive(Debug, Serialize, Deserialize)]
+struct HintRecord<K> {
+	key: K,
+	offset: u64,
+	length: u64,
+}
+
+/// The `KvStore` stores key/value pairs in an append-only log on disk,
+/// indexed in memory by a `HashMap`.
 ///
-/// Key/value pairs are stored in a `HashMap` in memory and not persisted to disk.
-pub struct KvStore {
+/// Generic over the key type `K`, the value type `V`, and the wire `Codec`
+/// used to serialize log records; all three default to the original
+/// `String`/`String`/`JsonCodec` combination, so existing callers are
+/// unaffected.
+pub struct KvStore<K = String, V = String, C = JsonCodec>
+where
+	K: Serialize + DeserializeOwned + Eq + Hash + Clone,
+{
 	path: PathBuf,
 	generation: u64,
-	index: KvIndex,
+	index: KvIndex<K>,
 	writer: KvWriter,
 	readers: HashMap<u64, KvReader>,
+	/// Zero-copy read path for every generation, including the active one,
+	/// keyed the same as `readers`. The active generation's entry is
+	/// invalidated after every write (see `invalidate_active_mmap`) and
+	/// lazily re-mapped by `get` on the next lookup, so `get` never falls
+	/// back to a buffered read.
+	mmaps: HashMap<u64, Mmap>,
 	compactable: u64,
+	/// Set when the store was opened with `open_encrypted`; every log
+	/// payload is sealed/opened through it before it ever touches a writer
+	/// or reader.
+	encryption: Option<Encryption>,
+	_value: PhantomData<V>,
+	_codec: PhantomData<C>,
 }
 
-impl KvStore {
+impl<K, V, C> KvStore<K, V, C>
+where
+	K: Serialize + DeserializeOwned + Eq + Hash + Clone,
+	V: Serialize + DeserializeOwned,
+	C: Codec,
+{
 	/// Opens a KvStore from a file
-	pub fn open(path: impl Into<PathBuf>) -> Result<KvStore> {
+	pub fn open(path: impl Into<PathBuf>) -> Result<KvStore<K, V, C>> {
+		KvStore::open_with(path, None)
+	}
+
+	/// Opens a `KvStore` whose log values are encrypted at rest, deriving a
+	/// 256-bit key from `passphrase` via Argon2.
+	///
+	/// The first time a store is created, a random salt is generated and
+	/// persisted alongside `cipher` in a `keyfile` in `path`; later opens
+	/// reuse the recorded salt and cipher, so `cipher` only matters for a
+	/// brand-new store.
+	pub fn open_encrypted(
+		path: impl Into<PathBuf>,
+		passphrase: &str,
+		cipher: Cipher,
+	) -> Result<KvStore<K, V, C>> {
+		let path = path.into();
+		fs::create_dir_all(&path)?;
+		let encryption = Encryption::open(&path, passphrase, cipher)?;
+		KvStore::open_with(path, Some(encryption))
+	}
+
+	fn open_with(
+		path: impl Into<PathBuf>,
+		encryption: Option<Encryption>,
+	) -> Result<KvStore<K, V, C>> {
 		let path = path.into();
 		fs::create_dir_all(&path)?;
 
 		let mut readers = HashMap::new();
+		let mut mmaps = HashMap::new();
 		let mut index = KvIndex::new();
 		let mut compactable = 0;
+		let pre_migration_generations = active_generations(&path)?;
+		migrate_legacy_store::<K, V, C>(&path, &pre_migration_generations, encryption.as_ref())?;
 		let generations = active_generations(&path)?;
 		let generation = *generations.last().unwrap_or(&0);
 
@@ -82,7 +316,20 @@ impl KvStore {
 		} else {
 			for &gen in &generations {
 				let mut reader = KvReader::new(&generation_path(&path, gen))?;
-				compactable += load(&mut index, generation, &mut reader)?;
+				match load_hint(&mut index, gen, &path)? {
+					Some(hint_compactable) => compactable += hint_compactable,
+					None => {
+						compactable += load::<K, V, C>(
+							&mut index,
+							gen,
+							&mut reader,
+							encryption.as_ref(),
+						)?;
+					}
+				}
+				if gen != generation {
+					mmaps.insert(gen, mmap_generation(&generation_path(&path, gen))?);
+				}
 				readers.insert(gen, reader);
 			}
 		}
@@ -93,19 +340,29 @@ impl KvStore {
 			index,
 			writer,
 			readers,
+			mmaps,
 			compactable,
+			encryption,
+			_value: PhantomData,
+			_codec: PhantomData,
 		})
 	}
 
-	/// Sets the value of a string key to a string.
+	/// Sets the value of a key.
 	///
 	/// If the key already exists, the previous value will be overwritten.
-	pub fn set(&mut self, key: String, value: String) -> Result<()> {
+	pub fn set(&mut self, key: K, value: V) -> Result<()> {
 		let action = KvAction::Set(key.clone(), value);
+		let plaintext = C::encode(&action)?;
+		let payload = match &self.encryption {
+			Some(encryption) => encryption.seal(&plaintext),
+			None => plaintext,
+		};
 		let offset = self.writer.pos;
-		serde_json::to_writer(&mut self.writer, &action)?;
+		write_frame(&mut self.writer, &payload)?;
 		let length = self.writer.pos - offset;
 		self.writer.flush()?;
+		self.invalidate_active_mmap();
 		let generation = self.generation;
 		let record = KvIndexRecord {
 			generation,
@@ -123,39 +380,60 @@ impl KvStore {
 		Ok(())
 	}
 
-	/// Gets the string value of a given string key.
+	/// Gets the value of a given key.
 	///
 	/// Returns `None` if the given key does not exist.
-	pub fn get(&mut self, key: String) -> Result<Option<String>> {
-		let index = &self.index;
-		if let Some(KvIndexRecord {
+	pub fn get(&mut self, key: K) -> Result<Option<V>> {
+		let record = match self.index.get(&key) {
+			Some(record) => *record,
+			None => return Ok(None),
+		};
+		let KvIndexRecord {
 			generation,
 			offset,
 			length,
-		}) = index.get(&key)
-		{
-			let reader = self
-				.readers
-				.get_mut(generation)
-				.expect("No reader for generation");
-			reader.seek(SeekFrom::Start(*offset))?;
-			let take = reader.take(*length);
-			if let KvAction::Set(_key, value) = serde_json::from_reader(take)? {
-				Ok(Some(value))
-			} else {
-				Err(KvError::UnexpectedAction)
-			}
+		} = record;
+
+		// The active generation's mmap is invalidated after every write
+		// (see `set`/`remove`/`compact`) and lazily rebuilt here, so even a
+		// lookup into the generation still being appended to goes through
+		// the zero-copy path and reflects the latest flushed bytes.
+		if !self.mmaps.contains_key(&generation) {
+			let mmap = mmap_generation(&generation_path(&self.path, generation))?;
+			self.mmaps.insert(generation, mmap);
+		}
+		let mmap = self
+			.mmaps
+			.get(&generation)
+			.expect("No mmap for generation");
+		let start = offset as usize;
+		let end = start + length as usize;
+		let mut slice = &mmap[start..end];
+		let payload = read_frame(&mut slice)?.ok_or(KvError::UnexpectedAction)?;
+		let plaintext = match &self.encryption {
+			Some(encryption) => encryption.open_frame(&payload)?,
+			None => payload,
+		};
+
+		if let KvAction::Set(_key, value) = C::decode::<KvAction<K, V>>(&plaintext)? {
+			Ok(Some(value))
 		} else {
-			Ok(None)
+			Err(KvError::UnexpectedAction)
 		}
 	}
 
-	/// Remove a given key.
-	pub fn remove(&mut self, key: String) -> Result<()> {
+	/// Removes a given key.
+	pub fn remove(&mut self, key: K) -> Result<()> {
 		if self.index.contains_key(&key) {
-			let action = KvAction::Remove(key);
-			serde_json::to_writer(&mut self.writer, &action)?;
+			let action: KvAction<K, V> = KvAction::Remove(key);
+			let plaintext = C::encode(&action)?;
+			let payload = match &self.encryption {
+				Some(encryption) => encryption.seal(&plaintext),
+				None => plaintext,
+			};
+			write_frame(&mut self.writer, &payload)?;
 			self.writer.flush()?;
+			self.invalidate_active_mmap();
 			if let KvAction::Remove(key) = action {
 				let outdated = self.index.remove(&key).expect("key not found");
 				self.compactable += outdated.length
@@ -166,7 +444,10 @@ impl KvStore {
 		}
 	}
 
-	/// walk
+	/// Rewrites every live record into a fresh generation, drops the
+	/// readers/mmaps for and deletes the superseded generations, reclaiming
+	/// the dead bytes counted in `compactable`, then writes the fresh
+	/// generation's hint file.
 	fn compact(&mut self) -> Result<()> {
 		self.generation += 1;
 		let new_path = generation_path(&self.path, self.generation);
@@ -188,15 +469,16 @@ impl KvStore {
 				.expect("No reader for generation");
 
 			reader.seek(SeekFrom::Start(*offset))?;
-			reader.take(*offset);
+			let mut take = reader.take(*length);
 
-			io::copy(&mut reader, &mut self.writer)?;
+			io::copy(&mut take, &mut self.writer)?;
 
 			entry.generation = self.generation;
 			entry.offset = self.writer.pos - *length;
 		}
 
 		self.writer.flush()?;
+		self.invalidate_active_mmap();
 
 		let removable: Vec<_> = self
 			.readers
@@ -207,24 +489,147 @@ impl KvStore {
 
 		for generation in removable {
 			self.readers.remove(&generation);
+			self.mmaps.remove(&generation);
 			fs::remove_file(generation_path(&self.path, generation))?;
+			let _ = fs::remove_file(hint_path(&self.path, generation));
 		}
 
 		self.compactable = 0;
 
+		write_hint(&self.index, &self.path, self.generation)?;
+
 		Ok(())
 	}
+
+	/// Drops the active generation's cached mmap after a flush, since an
+	/// `mmap` created before a write doesn't grow to cover data appended
+	/// afterwards. `get`'s lazy `if !self.mmaps.contains_key` check re-maps
+	/// it from the now-current file on the next lookup, so a run of writes
+	/// with no intervening read pays no extra `mmap` syscalls.
+	fn invalidate_active_mmap(&mut self) {
+		self.mmaps.remove(&self.generation);
+	}
+}
+
+impl KvsEngine for KvStore<String, String, JsonCodec> {
+	fn set(&mut self, key: String, value: String) -> Result<()> {
+		KvStore::set(self, key, value)
+	}
+
+	fn get(&mut self, key: String) -> Result<Option<String>> {
+		KvStore::get(self, key)
+	}
+
+	fn remove(&mut self, key: String) -> Result<()> {
+		KvStore::remove(self, key)
+	}
+}
+
+impl<K, V, C> Drop for KvStore<K, V, C>
+where
+	K: Serialize + DeserializeOwned + Eq + Hash + Clone,
+{
+	fn drop(&mut self) {
+		let _ = write_hint(&self.index, &self.path, self.generation);
+	}
+}
+
+/// Attempts to populate `index` with the live entries for `generation` from
+/// its `<generation>.hint` file instead of replaying the log. Returns the
+/// reconstructed dead-byte count (the on-disk generation size minus the sum
+/// of the live record lengths listed in the hint) if the hint was present,
+/// at least as new as the `.db` file, and parsed cleanly; returns `None`
+/// (leaving `index` untouched) so the caller can fall back to `load`.
+fn load_hint<K>(index: &mut KvIndex<K>, generation: u64, dirpath: &PathBuf) -> Result<Option<u64>>
+where
+	K: Serialize + DeserializeOwned + Eq + Hash + Clone,
+{
+	let hint_path = hint_path(dirpath, generation);
+	let hint_meta = match fs::metadata(&hint_path) {
+		Ok(meta) => meta,
+		Err(_) => return Ok(None),
+	};
+	let db_meta = fs::metadata(generation_path(dirpath, generation))?;
+	match (hint_meta.modified(), db_meta.modified()) {
+		(Ok(hint_mtime), Ok(db_mtime)) if hint_mtime < db_mtime => return Ok(None),
+		(Ok(_), Ok(_)) => (),
+		_ => return Ok(None),
+	}
+
+	let file = match fs::File::open(&hint_path) {
+		Ok(file) => file,
+		Err(_) => return Ok(None),
+	};
+	let stream = Deserializer::from_reader(BufReader::new(file)).into_iter::<HintRecord<K>>();
+	let mut records = Vec::new();
+	for record in stream {
+		match record {
+			Ok(record) => records.push(record),
+			Err(_) => return Ok(None),
+		}
+	}
+
+	let live_bytes: u64 = records.iter().map(|record| record.length).sum();
+	for HintRecord { key, offset, length } in records {
+		index.insert(
+			key,
+			KvIndexRecord {
+				generation,
+				offset,
+				length,
+			},
+		);
+	}
+
+	Ok(Some(db_meta.len().saturating_sub(live_bytes)))
+}
+
+/// Writes the `<generation>.hint` file for the live entries currently
+/// pointing at `generation`, so a future `open` can skip replaying it.
+fn write_hint<K>(index: &KvIndex<K>, dirpath: &PathBuf, generation: u64) -> Result<()>
+where
+	K: Serialize + Eq + Hash + Clone,
+{
+	let file = fs::File::create(hint_path(dirpath, generation))?;
+	let mut writer = BufWriter::new(file);
+	for (key, record) in index {
+		if record.generation != generation {
+			continue;
+		}
+		let hint_record = HintRecord {
+			key: key.clone(),
+			offset: record.offset,
+			length: record.length,
+		};
+		serde_json::to_writer(&mut writer, &hint_record)?;
+	}
+	writer.flush()?;
+	Ok(())
 }
 
-fn load(index: &mut KvIndex, generation: u64, reader: &mut KvReader) -> Result<u64> {
-	let mut stream = Deserializer::from_reader(reader).into_iter::<KvAction>();
+fn load<K, V, C>(
+	index: &mut KvIndex<K>,
+	generation: u64,
+	reader: &mut KvReader,
+	encryption: Option<&Encryption>,
+) -> Result<u64>
+where
+	K: Eq + Hash + Clone + DeserializeOwned,
+	V: DeserializeOwned,
+	C: Codec,
+{
 	let mut offset = 0;
 	let mut compactable = 0;
 
-	while let Some(action) = stream.next() {
-		let next_offset = stream.byte_offset() as u64;
-		let length = next_offset - offset;
-		match action? {
+	while let Some(payload) = read_frame(reader)? {
+		let length = FRAME_HEADER_LEN + payload.len() as u64;
+		let next_offset = offset + length;
+		let plaintext = match encryption {
+			Some(encryption) => encryption.open_frame(&payload)?,
+			None => payload,
+		};
+		let action = C::decode::<KvAction<K, V>>(&plaintext)?;
+		match action {
 			KvAction::Set(key, _value) => {
 				let record = KvIndexRecord {
 					generation,
@@ -248,3 +653,104 @@ fn load(index: &mut KvIndex, generation: u64, reader: &mut KvReader) -> Result<u
 
 	Ok(compactable)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn hint_reload_matches_full_replay() {
+		let dir = TempDir::new().unwrap();
+		{
+			let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+			store.set("a".to_owned(), "1".to_owned()).unwrap();
+			store.set("b".to_owned(), "2".to_owned()).unwrap();
+			store.remove("a".to_owned()).unwrap();
+			store.set("c".to_owned(), "3".to_owned()).unwrap();
+		}
+
+		let via_hint = {
+			let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+			(
+				store.get("a".to_owned()).unwrap(),
+				store.get("b".to_owned()).unwrap(),
+				store.get("c".to_owned()).unwrap(),
+			)
+		};
+
+		fs::remove_file(dir.path().join("0.hint")).unwrap();
+
+		let via_replay = {
+			let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+			(
+				store.get("a".to_owned()).unwrap(),
+				store.get("b".to_owned()).unwrap(),
+				store.get("c".to_owned()).unwrap(),
+			)
+		};
+
+		assert_eq!(via_hint, via_replay);
+		assert_eq!(via_replay, (None, Some("2".to_owned()), Some("3".to_owned())));
+	}
+
+	#[test]
+	fn corrupted_payload_is_detected() {
+		let dir = TempDir::new().unwrap();
+		{
+			let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+			store.set("a".to_owned(), "1".to_owned()).unwrap();
+		}
+		fs::remove_file(dir.path().join("0.hint")).unwrap();
+
+		let db_path = dir.path().join("0.db");
+		let mut bytes = fs::read(&db_path).unwrap();
+		let last = bytes.len() - 1;
+		bytes[last] ^= 0xff;
+		fs::write(&db_path, bytes).unwrap();
+
+		let result: Result<KvStore> = KvStore::open(dir.path());
+		assert!(matches!(result, Err(KvError::ChecksumMismatch)));
+	}
+
+	#[test]
+	fn read_frame_rejects_an_implausible_length_before_allocating() {
+		let mut header = Vec::new();
+		header.extend_from_slice(&u32::MAX.to_le_bytes());
+		header.extend_from_slice(&0u32.to_le_bytes());
+
+		let mut reader = &header[..];
+		assert!(matches!(
+			read_frame(&mut reader),
+			Err(KvError::ChecksumMismatch)
+		));
+	}
+
+	#[test]
+	fn get_sees_writes_to_the_active_generation() {
+		let dir = TempDir::new().unwrap();
+		let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+		store.set("a".to_owned(), "1".to_owned()).unwrap();
+		assert_eq!(store.get("a".to_owned()).unwrap(), Some("1".to_owned()));
+
+		store.set("a".to_owned(), "2".to_owned()).unwrap();
+		assert_eq!(store.get("a".to_owned()).unwrap(), Some("2".to_owned()));
+	}
+
+	#[test]
+	fn already_framed_store_missing_its_version_marker_reopens_without_migrating() {
+		let dir = TempDir::new().unwrap();
+		{
+			let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+			store.set("a".to_owned(), "1".to_owned()).unwrap();
+			store.set("b".to_owned(), "2".to_owned()).unwrap();
+		}
+		// Simulate a store written by a build that already framed records
+		// but predates the `version` marker.
+		fs::remove_file(dir.path().join("version")).unwrap();
+
+		let mut store: KvStore = KvStore::open(dir.path()).unwrap();
+		assert_eq!(store.get("a".to_owned()).unwrap(), Some("1".to_owned()));
+		assert_eq!(store.get("b".to_owned()).unwrap(), Some("2".to_owned()));
+	}
+}
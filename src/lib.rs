@@ -1,10 +1,33 @@
 #![deny(missing_docs)]
 //! A simple key/value store.
 
+pub use codec::{BincodeCodec, Codec, JsonCodec};
+pub use crypto::Cipher;
 pub use error::{KvError, Result};
 pub use kv::KvStore;
+pub use memory::MemoryKvStore;
 
+mod codec;
+mod crypto;
 mod error;
 mod kv;
 mod kvreader;
 mod kvwriter;
+mod memory;
+
+/// A pluggable storage-engine interface implemented by `KvStore` and
+/// `MemoryKvStore`, so callers can pick a backend at construction time.
+pub trait KvsEngine {
+	/// Sets the value of a string key to a string.
+	///
+	/// If the key already exists, the previous value will be overwritten.
+	fn set(&mut self, key: String, value: String) -> Result<()>;
+
+	/// Gets the string value of a given string key.
+	///
+	/// Returns `None` if the given key does not exist.
+	fn get(&mut self, key: String) -> Result<Option<String>>;
+
+	/// Removes a given key.
+	fn remove(&mut self, key: String) -> Result<()>;
+}
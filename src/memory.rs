@@ -0,0 +1,61 @@
+use crate::{KvError, KvsEngine, Result};
+use std::collections::HashMap;
+
+/// An in-memory-only `KvsEngine` backed by a plain `HashMap`.
+///
+/// Values are never persisted to disk: there is no `KvReader`/`KvWriter` and
+/// no compaction. Useful for testing against the `KvsEngine` trait without
+/// touching the filesystem.
+#[derive(Debug, Default)]
+pub struct MemoryKvStore {
+	map: HashMap<String, String>,
+}
+
+impl MemoryKvStore {
+	/// Creates an empty `MemoryKvStore`.
+	pub fn new() -> MemoryKvStore {
+		MemoryKvStore::default()
+	}
+}
+
+impl KvsEngine for MemoryKvStore {
+	fn set(&mut self, key: String, value: String) -> Result<()> {
+		self.map.insert(key, value);
+		Ok(())
+	}
+
+	fn get(&mut self, key: String) -> Result<Option<String>> {
+		Ok(self.map.get(&key).cloned())
+	}
+
+	fn remove(&mut self, key: String) -> Result<()> {
+		match self.map.remove(&key) {
+			Some(_) => Ok(()),
+			None => Err(KvError::KeyNotFound),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn set_get_remove_round_trip() {
+		let mut store = MemoryKvStore::new();
+		store.set("a".to_owned(), "1".to_owned()).unwrap();
+		store.set("b".to_owned(), "2".to_owned()).unwrap();
+		store.set("a".to_owned(), "3".to_owned()).unwrap();
+
+		assert_eq!(store.get("a".to_owned()).unwrap(), Some("3".to_owned()));
+		assert_eq!(store.get("b".to_owned()).unwrap(), Some("2".to_owned()));
+		assert_eq!(store.get("c".to_owned()).unwrap(), None);
+
+		store.remove("a".to_owned()).unwrap();
+		assert_eq!(store.get("a".to_owned()).unwrap(), None);
+		assert!(matches!(
+			store.remove("a".to_owned()),
+			Err(KvError::KeyNotFound)
+		));
+	}
+}